@@ -0,0 +1,131 @@
+// Minimal `ar` archive reader, just enough to walk the ELF members of a
+// `.a` static library. Handles both the GNU and BSD long-filename
+// conventions, plus GNU thin archives, mirroring the archive reader
+// subsystem in the `object` crate.
+
+use std::fs;
+use std::path::{Component, Path};
+
+const GLOBAL_MAGIC: &'static [u8] = b"!<arch>\n";
+const THIN_MAGIC: &'static [u8] = b"!<thin>\n";
+const HEADER_LEN: usize = 60;
+
+pub struct Member {
+    pub name: String,
+    pub data: Vec<u8>,
+}
+
+pub fn is_archive(buf: &[u8]) -> bool {
+    buf.starts_with(GLOBAL_MAGIC) || buf.starts_with(THIN_MAGIC)
+}
+
+// A truncated or corrupted `.a` is realistic input for a scanner that
+// walks arbitrary trees, so every slice derived from header-supplied
+// offsets/sizes goes through this instead of raw indexing -- out-of-range
+// input becomes an `Err`, not a panic.
+fn checked_slice<'a>(buf: &'a [u8], start: usize, end: usize) -> Result<&'a [u8], String> {
+    if start > end || end > buf.len() {
+        return Err("truncated or corrupt ar archive".to_string());
+    }
+    Ok(&buf[start..end])
+}
+
+// Thin-archive member names come straight out of the `ar` header and are
+// joined onto `base_dir` to find the real file on disk, so a name with a
+// `..` component or an absolute path would let a malicious archive read
+// files outside its own directory (CWE-22, the zip-slip class of bug).
+fn is_safe_member_name(name: &str) -> bool {
+    Path::new(name).components().all(|c| match c {
+        Component::Normal(_) | Component::CurDir => true,
+        Component::ParentDir | Component::RootDir | Component::Prefix(_) => false,
+    })
+}
+
+// Parses a GNU or BSD `ar` archive (including thin archives) into its
+// member objects. `base_dir` is the directory the archive itself lives in,
+// used to resolve thin-archive member references on disk.
+pub fn read_members<P: AsRef<Path>>(buf: &[u8], base_dir: P) -> Result<Vec<Member>, String> {
+    let base_dir = base_dir.as_ref();
+    let thin = buf.starts_with(THIN_MAGIC);
+    if !thin && !buf.starts_with(GLOBAL_MAGIC) {
+        return Err("not an ar archive".to_string());
+    }
+
+    let mut pos = GLOBAL_MAGIC.len();
+    let mut long_names: Vec<u8> = Vec::new();
+    let mut members = Vec::new();
+
+    while pos + HEADER_LEN <= buf.len() {
+        let header = &buf[pos..pos + HEADER_LEN];
+        pos += HEADER_LEN;
+
+        if &header[58..60] != b"`\n" {
+            return Err("malformed ar member header".to_string());
+        }
+
+        let raw_name = try!(std::str::from_utf8(&header[0..16]).map_err(|e| e.to_string())).trim_end();
+        let size: usize = try!(
+            try!(std::str::from_utf8(&header[48..58]).map_err(|e| e.to_string()))
+                .trim()
+                .parse()
+                .map_err(|_| "bad ar member size".to_string())
+        );
+
+        // GNU long-filename table: every overflowing name, each terminated
+        // by "/\n", referenced later as "/<offset>".
+        if raw_name == "//" {
+            long_names = try!(checked_slice(buf, pos, pos + size)).to_vec();
+            pos += size + (size % 2);
+            continue;
+        }
+
+        // GNU symbol table(s) -- not a real member, just an index.
+        if raw_name == "/" || raw_name == "/SYM64/" {
+            pos += size + (size % 2);
+            continue;
+        }
+
+        let (name, inline_name_len) = if raw_name.starts_with("#1/") {
+            // BSD extended name: `name_len` bytes of inline name data
+            // immediately precede the member's actual contents.
+            let name_len: usize = try!(raw_name[3..].parse().map_err(|_| "bad BSD name length".to_string()));
+            let name_bytes = try!(checked_slice(buf, pos, pos + name_len));
+            let name = try!(std::str::from_utf8(name_bytes).map_err(|e| e.to_string()))
+                .trim_end_matches('\0')
+                .to_string();
+            (name, name_len)
+        } else if raw_name.starts_with('/') {
+            // GNU long name reference: "/<offset-into-longnames-table>".
+            let offset: usize = try!(raw_name[1..].parse().map_err(|_| "bad long name offset".to_string()));
+            let rest = try!(checked_slice(&long_names, offset, long_names.len()));
+            // Entries are terminated by "/\n", not a bare '/' -- a name that
+            // legitimately contains a slash would otherwise get truncated at
+            // that slash instead of its real terminator.
+            let end = rest.windows(2).position(|w| w == b"/\n").map(|p| offset + p).unwrap_or(
+                long_names.len(),
+            );
+            let name_bytes = try!(checked_slice(&long_names, offset, end));
+            let name = try!(std::str::from_utf8(name_bytes).map_err(|e| e.to_string())).to_string();
+            (name, 0)
+        } else {
+            (raw_name.trim_end_matches('/').to_string(), 0)
+        };
+
+        if thin && inline_name_len == 0 {
+            // Thin archives store no bytes for ordinary members -- only
+            // the name and metadata -- so resolve the real contents
+            // against the archive's own directory instead of the buffer.
+            if !is_safe_member_name(&name) {
+                return Err(format!("unsafe thin archive member path: {}", name));
+            }
+            let data = try!(fs::read(base_dir.join(&name)).map_err(|e| e.to_string()));
+            members.push(Member { name: name, data: data });
+        } else {
+            let data = try!(checked_slice(buf, pos + inline_name_len, pos + size)).to_vec();
+            members.push(Member { name: name, data: data });
+            pos += size + (size % 2);
+        }
+    }
+
+    Ok(members)
+}
@@ -0,0 +1,122 @@
+// Support for SHF_COMPRESSED sections (the generic ELF compression scheme,
+// as opposed to the older .zdebug naming convention). Stripped/packaged
+// binaries sometimes ship .symtab/.dynsym this way, and without inflating
+// them first `fortify_fns` and the canary check cannot read them at all.
+
+use std::io::Read;
+use xmas_elf::header::Class;
+use xmas_elf::sections::SectionHeader;
+use xmas_elf::ElfFile;
+
+const SHF_COMPRESSED: u64 = 0x800;
+const ELFCOMPRESS_ZLIB: u32 = 1;
+
+// `ch_size` is attacker-controlled (it comes straight out of the section
+// being scanned), so neither it nor the actual zlib output can be trusted
+// unbounded -- cap both, or a tiny crafted section can claim/produce an
+// arbitrarily large uncompressed size and OOM the scanner (CWE-409).
+const MAX_DECOMPRESSED_SIZE: usize = 256 * 1024 * 1024;
+
+fn read_u32_le(buf: &[u8], offset: usize) -> u32 {
+    (buf[offset] as u32) | (buf[offset + 1] as u32) << 8 | (buf[offset + 2] as u32) << 16 |
+        (buf[offset + 3] as u32) << 24
+}
+
+fn read_u64_le(buf: &[u8], offset: usize) -> u64 {
+    (read_u32_le(buf, offset) as u64) | ((read_u32_le(buf, offset + 4) as u64) << 32)
+}
+
+// Whether a section is flagged SHF_COMPRESSED. Callers must check this
+// *before* handing the section to `get_data` -- its still-compressed raw
+// bytes aren't a valid symbol/dynamic table and will make `get_data` panic
+// rather than return an empty result.
+pub fn is_compressed(sect: &SectionHeader) -> bool {
+    sect.flags() & SHF_COMPRESSED != 0
+}
+
+// Inflates a SHF_COMPRESSED section's raw bytes, having first parsed the
+// leading Elf32_Chdr/Elf64_Chdr to learn the algorithm and uncompressed
+// size. Returns `None` if the section isn't actually compressed, or uses
+// an algorithm other than zlib.
+fn inflate<'a>(sect: &SectionHeader<'a>, elf_file: &ElfFile<'a>) -> Option<Vec<u8>> {
+    if !is_compressed(sect) {
+        return None;
+    }
+
+    let raw = sect.raw_data(elf_file);
+
+    // Elf64_Chdr: ch_type(4) ch_reserved(4) ch_size(8) ch_addralign(8).
+    // Elf32_Chdr: ch_type(4) ch_size(4) ch_addralign(4).
+    let (hdr_len, ch_type, decompressed_size) = if elf_file.header.pt1.class() == Class::SixtyFour {
+        if raw.len() < 24 {
+            return None;
+        }
+        (24, read_u32_le(raw, 0), read_u64_le(raw, 8) as usize)
+    } else {
+        if raw.len() < 12 {
+            return None;
+        }
+        (12, read_u32_le(raw, 0), read_u32_le(raw, 4) as usize)
+    };
+
+    if ch_type != ELFCOMPRESS_ZLIB {
+        return None;
+    }
+
+    if decompressed_size > MAX_DECOMPRESSED_SIZE {
+        return None;
+    }
+
+    // Read one byte past the cap so genuine bombs (real output bigger than
+    // the cap, whatever `ch_size` claimed) are detected and rejected rather
+    // than silently handed back truncated.
+    let mut decoder = ::flate2::read::ZlibDecoder::new(&raw[hdr_len..]).take(MAX_DECOMPRESSED_SIZE as u64 + 1);
+    let mut out = Vec::with_capacity(decompressed_size);
+    match decoder.read_to_end(&mut out) {
+        Ok(_) if out.len() > MAX_DECOMPRESSED_SIZE => None,
+        Ok(_) => Some(out),
+        Err(_) => None,
+    }
+}
+
+fn read_cstr(strtab: &[u8], offset: usize) -> Option<String> {
+    if offset >= strtab.len() {
+        return None;
+    }
+    let end = match strtab[offset..].iter().position(|&b| b == 0) {
+        Some(p) => offset + p,
+        None => return None,
+    };
+    std::str::from_utf8(&strtab[offset..end]).ok().map(|s| s.to_string())
+}
+
+// Inflates `sect` (assumed to be a SHT_SYMTAB/SHT_DYNSYM section) and
+// decodes its Elf32_Sym/Elf64_Sym entries by hand, since `get_data` only
+// knows how to parse a section's *uncompressed* bytes into a symbol table.
+pub fn symbol_names<'a>(sect: &SectionHeader<'a>, elf_file: &ElfFile<'a>) -> Vec<String> {
+    let data = match inflate(sect, elf_file) {
+        Some(d) => d,
+        None => return vec![],
+    };
+
+    let strtab_sect = match elf_file.section_iter().nth(sect.link() as usize) {
+        Some(s) => s,
+        None => return vec![],
+    };
+    let strtab = inflate(&strtab_sect, elf_file).unwrap_or_else(|| strtab_sect.raw_data(elf_file).to_vec());
+
+    // Elf64_Sym: st_name(4) st_info(1) st_other(1) st_shndx(2) st_value(8)
+    // st_size(8) = 24 bytes. Elf32_Sym: st_name(4) st_value(4) st_size(4)
+    // st_info(1) st_other(1) st_shndx(2) = 16 bytes. Either way st_name is
+    // the leading 4-byte field.
+    let entry_size = if elf_file.header.pt1.class() == Class::SixtyFour {
+        24
+    } else {
+        16
+    };
+
+    data.chunks(entry_size)
+        .filter(|c| c.len() == entry_size)
+        .filter_map(|c| read_cstr(&strtab, read_u32_le(c, 0) as usize))
+        .collect()
+}